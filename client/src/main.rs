@@ -1,7 +1,7 @@
 //! A simplified implementation of the classic game "Breakout".
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, VecDeque},
     ops::{Add, Mul},
 };
 
@@ -17,14 +17,41 @@ use uuid::Uuid;
 #[cfg(target_arch = "wasm32")]
 use web_sys;
 
+// Real collision against walls, the ground plane, and bricks, gated behind the
+// `physics` feature so the default WASM build keeps the manual `collide_player`
+// check. Aliased to avoid clashing with our own marker `Collider`/`CollisionEvent`.
+#[cfg(feature = "physics")]
+use bevy_rapier3d::prelude::{
+    ActiveEvents, Collider as RapierCollider, CollisionEvent as RapierCollisionEvent,
+    NoUserData, RapierPhysicsPlugin, RigidBody, Velocity as RapierVelocity,
+};
+
 // The shared library between server and client
-use shared::GameClient;
+use shared::{
+    apply_input, AuthoritativeState, ClientId, GameClient, Local, NetPosition, PlayerInput, Remote, StateSync,
+    FIXED_DT, INPUT_DELAY_FRAMES, MAX_PREDICTION_FRAMES, PLAYER_SPEED,
+};
+
+// `physics` drops `rollback_player`/`reconcile_with_server` from the schedule (see the
+// comment above `RapierPhysicsPlugin` below), which breaks networked determinism: a
+// `physics` build can't reconcile against the server or roll back a misprediction.
+// That's a silent, easy-to-miss downgrade for anyone who just wanted real collision,
+// so building with `physics` alone is a compile error; `physics_unsynced_rollback`
+// must be enabled alongside it as an explicit acknowledgement of the tradeoff.
+#[cfg(all(feature = "physics", not(feature = "physics_unsynced_rollback")))]
+compile_error!(
+    "the `physics` feature drops rollback/reconciliation from the schedule, which breaks \
+     networked determinism; enable `physics_unsynced_rollback` alongside `physics` to build \
+     anyway"
+);
+
+/// How long to wait after a disconnect before attempting to reconnect.
+const RECONNECT_DELAY_SECS: f32 = 3.0;
 
 // These constants are defined in `Transform` units.
 // Using the default 2D camera they correspond 1:1 with screen pixels.
 const PLAYER_SIZE: Vec3 = Vec3::new(120.0, 120.0, 0.0);
 const GAP_BETWEEN_PLAYER_AND_FLOOR: f32 = 60.0;
-const PLAYER_SPEED: f32 = 50.0;
 const PLAYER_COLOR: Color = Color::srgb(0.3, 0.3, 0.7);
 
 // Map constants
@@ -57,27 +84,122 @@ fn get_window_plugin() -> WindowPlugin {
 
 fn main() {
     // Start app
-    App::new()
-        .add_plugins(DefaultPlugins.set(get_window_plugin()))
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(get_window_plugin()))
         .insert_resource(ClearColor(BACKGROUND_COLOR))
         .insert_resource(ClientPositions { map: default() })
+        .insert_resource(CurrentFrame::default())
+        .insert_resource(ServerClock::default())
+        .insert_resource(LocalInputBuffer::default())
+        .insert_resource(RemoteInputBuffer::default())
+        .insert_resource(SnapshotBuffer::default())
+        .insert_resource(ReconnectTimer(Timer::from_seconds(
+            RECONNECT_DELAY_SECS,
+            TimerMode::Once,
+        )))
+        .init_state::<AppState>()
+        .add_event::<ServerStateReceived>()
+        .add_event::<StateSyncReceived>()
+        .add_event::<ClientJoined>()
+        .add_event::<ClientLeft>()
+        .add_event::<SocketClosed>()
+        .add_event::<CollisionEvent>()
         .add_systems(Startup, (setup, setup_map))
+        .add_systems(OnEnter(AppState::Connecting), spawn_connecting_status_ui)
+        .add_systems(OnExit(AppState::Connecting), despawn_connection_status_ui)
+        .add_systems(
+            OnEnter(AppState::Disconnected),
+            (
+                spawn_disconnected_status_ui,
+                reset_reconnect_timer,
+                clear_stale_remote_players,
+            ),
+        )
+        .add_systems(OnExit(AppState::Disconnected), despawn_connection_status_ui)
         // Add our gameplay simulation systems to the fixed timestep schedule
-        // which runs at 64 Hz by default
+        // which runs at 64 Hz by default. Rollback netcode depends on this rate
+        // matching `shared::FIXED_HZ` exactly, since frame numbers are compared
+        // directly against the server's. The per-frame `Update` systems come first
+        // here; the `FixedUpdate` chain itself is added further down.
+        .add_systems(
+            Update,
+            (
+                handle_zoom,
+                on_resize_system,
+                start_connecting.run_if(in_state(AppState::MainMenu)),
+                decode_state_sync,
+                enter_game_on_first_sync
+                    .after(decode_state_sync)
+                    .run_if(in_state(AppState::Connecting)),
+                handle_socket_closed,
+                tick_reconnect_timer.run_if(in_state(AppState::Disconnected)),
+                sync_clients_to_players
+                    .after(decode_state_sync)
+                    .run_if(in_state(AppState::InGame)),
+                update_existing_player_positions.run_if(in_state(AppState::InGame)),
+            ),
+        );
+
+    // Without `bevy_rapier3d`, movement stays fully manual: write `Transform`
+    // directly so rollback's snapshot/restore keeps working exactly as before.
+    //
+    // `move_player`/`collide_player` are deliberately NOT gated on `AppState::InGame`:
+    // until the client actually opens a websocket (see the `TODO`s on
+    // `ServerStateReceived`/`StateSyncReceived`), `InGame` is unreachable, and gating
+    // local movement on it left the player unable to move at all. Gameplay that
+    // depends on *other* clients (remote player sync/interpolation, above) stays
+    // gated, since there's nothing to show until a connection exists.
+    #[cfg(not(feature = "physics"))]
+    app.add_systems(
+        FixedUpdate,
+        (
+            // apply_velocity,
+            advance_frame,
+            reconcile_with_server,
+            rollback_player,
+            buffer_local_input,
+            move_player,
+            collide_player,
+        )
+            // `chain`ing systems together runs them in order
+            .chain(),
+    );
+
+    // `bevy_rapier3d` replaces the manual `collide_player` distance check with real
+    // contacts against walls, the ground, and bricks. It's opt-in: the default WASM
+    // build doesn't pay for a physics pipeline it isn't using.
+    //
+    // Known gap: `rollback_player`/`reconcile_with_server` restore and resimulate by
+    // writing `Transform` directly, which a `RigidBody::Dynamic` won't respect once
+    // rapier is driving it from `RapierVelocity`. Wiring snapshot/restore through
+    // rapier's own state is follow-up work; for now the two netcode systems are left
+    // out of this chain rather than silently fighting the physics step.
+    #[cfg(feature = "physics")]
+    app.add_plugins(RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule())
         .add_systems(
             FixedUpdate,
             (
-                // apply_velocity,
-                move_player,
-                // sync_clients_to_players,
-                // update_existing_player_positions,
-                // collide_player,
+                advance_frame,
+                buffer_local_input,
+                move_player_physics,
+                sync_rapier_collisions,
             )
-                // `chain`ing systems together runs them in order
                 .chain(),
-        )
-        .add_systems(Update, (handle_zoom, on_resize_system))
-        .run();
+        );
+
+    app.run();
+}
+
+/// The client's coarse connection/gameplay phase. Remote-player sync and
+/// interpolation only run in `InGame`, and entering `Disconnected` clears stale
+/// remote players. Local movement and collision run in every state.
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+enum AppState {
+    #[default]
+    MainMenu,
+    Connecting,
+    InGame,
+    Disconnected,
 }
 
 #[derive(Component, Default)]
@@ -85,10 +207,11 @@ struct Player {
     colliding: bool,
 }
 
+/// Marks an entity as the visual for a remote player. Identity (`ClientId`) and
+/// position (`NetPosition`) live as their own components so systems that only need
+/// one don't have to query a bundled `GameClient` to get it.
 #[derive(Component)]
-struct OtherPlayer {
-    client: GameClient,
-}
+struct OtherPlayer;
 
 #[derive(Component)]
 struct Ball;
@@ -108,9 +231,382 @@ struct Brick;
 #[derive(Resource)]
 struct CollisionSound(Handle<AudioSource>);
 
+/// Per-remote-player ring buffer of `(tick, position)` snapshots, used to interpolate
+/// (or briefly extrapolate) smooth motion instead of snapping to the latest update.
 #[derive(Resource)]
 struct ClientPositions {
-    map: HashMap<Uuid, [f32; 2]>,
+    map: HashMap<Uuid, VecDeque<(u64, [f32; 2])>>,
+}
+
+impl ClientPositions {
+    fn push(&mut self, uuid: Uuid, tick: u64, position: [f32; 2]) {
+        let buffer = self.map.entry(uuid).or_default();
+        buffer.push_back((tick, position));
+        while buffer.len() > shared::SNAPSHOT_BUFFER_LEN {
+            buffer.pop_front();
+        }
+    }
+}
+
+/// Samples `buffer` at `target_tick`, interpolating between the bracketing snapshots,
+/// extrapolating a bounded distance past the newest one, or holding the oldest
+/// position if `target_tick` is older than everything we have.
+fn sample_position_at(buffer: &VecDeque<(u64, [f32; 2])>, target_tick: u64) -> Option<[f32; 2]> {
+    let &(newest_tick, newest_pos) = buffer.back()?;
+
+    if target_tick >= newest_tick {
+        let Some(&(prev_tick, prev_pos)) = buffer.iter().nth_back(1) else {
+            return Some(newest_pos);
+        };
+        let gap = target_tick - newest_tick;
+        if gap > shared::MAX_EXTRAPOLATION_FRAMES || newest_tick == prev_tick {
+            return Some(newest_pos);
+        }
+        // `t` > 1 here extrapolates the prev -> newest velocity forward by `gap`.
+        let t = (target_tick - prev_tick) as f32 / (newest_tick - prev_tick) as f32;
+        return Some(lerp_position(prev_pos, newest_pos, t));
+    }
+
+    // Find the pair of snapshots bracketing `target_tick`.
+    let mut windows = buffer.iter().zip(buffer.iter().skip(1));
+    if let Some((&(a_tick, a_pos), &(b_tick, b_pos))) =
+        windows.find(|(&(a, _), &(b, _))| a <= target_tick && target_tick <= b)
+    {
+        if a_tick == b_tick {
+            return Some(b_pos);
+        }
+        let t = (target_tick - a_tick) as f32 / (b_tick - a_tick) as f32;
+        return Some(lerp_position(a_pos, b_pos, t));
+    }
+
+    // `target_tick` is older than our whole buffer; hold the oldest snapshot.
+    buffer.front().map(|&(_, pos)| pos)
+}
+
+fn lerp_position(a: [f32; 2], b: [f32; 2], t: f32) -> [f32; 2] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+}
+
+/// The local simulation's current tick, advanced once per `FixedUpdate` step. Frame
+/// numbers are shared wire format with the server (see `shared::PlayerInput`), so
+/// rollback resimulation can line up local and remote history.
+#[derive(Resource, Default)]
+struct CurrentFrame(u64);
+
+/// Maps this client's `CurrentFrame` onto the server's global tick (`AuthoritativeState
+/// ::tick`), which `ClientPositions` snapshots are stamped with. The two counters start
+/// unrelated -- `CurrentFrame` counts from 0 since this client started, while the
+/// server's tick counts from when the server started -- so remote-player
+/// interpolation needs this offset to know which `CurrentFrame` corresponds to which
+/// server tick.
+#[derive(Resource, Default)]
+struct ServerClock {
+    /// `server_tick - CurrentFrame::0`, re-derived from every ack so the mapping
+    /// self-corrects instead of drifting if either clock hiccups.
+    offset: Option<i64>,
+}
+
+impl ServerClock {
+    fn sync(&mut self, local_frame: u64, server_tick: u64) {
+        self.offset = Some(server_tick as i64 - local_frame as i64);
+    }
+
+    /// Converts a local `CurrentFrame` value into the server's tick domain. Returns
+    /// `None` until the first ack has been received.
+    fn to_server_tick(&self, local_frame: u64) -> Option<u64> {
+        let offset = self.offset?;
+        Some((local_frame as i64 + offset).max(0) as u64)
+    }
+}
+
+/// Ring buffer of this client's own inputs, keyed by the frame they apply to. Kept
+/// around for `INPUT_DELAY_FRAMES` + `MAX_PREDICTION_FRAMES` frames so a rollback can
+/// replay them on top of a restored snapshot.
+#[derive(Resource, Default)]
+struct LocalInputBuffer {
+    inputs: VecDeque<PlayerInput>,
+}
+
+impl LocalInputBuffer {
+    fn push(&mut self, input: PlayerInput) {
+        self.inputs.push_back(input);
+        let cap = (INPUT_DELAY_FRAMES + MAX_PREDICTION_FRAMES) as usize;
+        while self.inputs.len() > cap {
+            self.inputs.pop_front();
+        }
+    }
+
+    fn get(&self, frame: u64) -> Option<PlayerInput> {
+        self.inputs.iter().find(|input| input.frame == frame).copied()
+    }
+
+    /// Drops every input up to and including `frame`, once the server has confirmed
+    /// it processed them.
+    fn prune_up_to(&mut self, frame: u64) {
+        self.inputs.retain(|input| input.frame > frame);
+    }
+}
+
+/// Fired when the server's authoritative state for the local player arrives.
+///
+/// TODO: raise this from the websocket connection once the client has one; nothing
+/// does yet, so `reconcile_with_server` never actually runs.
+#[derive(Event)]
+struct ServerStateReceived(AuthoritativeState);
+
+/// A raw `StateSync` frame as received over the (binary) websocket connection.
+///
+/// TODO: raise this from the websocket connection once the client has one; nothing
+/// does yet, so `decode_state_sync` never actually runs.
+#[derive(Event)]
+struct StateSyncReceived(Vec<u8>);
+
+/// A client the server told us about for the first time (either a fresh join, or one
+/// we're only now resyncing onto via a keyframe).
+#[derive(Event)]
+struct ClientJoined(GameClient);
+
+/// A client the server told us has disconnected.
+#[derive(Event)]
+struct ClientLeft(Uuid);
+
+/// Fired when the websocket connection drops, moving the client to `Disconnected`.
+///
+/// TODO: raise this from the websocket connection once the client has one; nothing
+/// does yet, so the client never leaves `InGame` on its own.
+#[derive(Event)]
+struct SocketClosed;
+
+/// Marks the UI text spawned for `AppState::Connecting`/`AppState::Disconnected`, so
+/// the matching `OnExit` system knows what to despawn.
+#[derive(Component)]
+struct ConnectionStatusText;
+
+/// Counts down while `AppState::Disconnected`, then kicks the client back to
+/// `Connecting` for another attempt.
+#[derive(Resource)]
+struct ReconnectTimer(Timer);
+
+fn spawn_connecting_status_ui(mut commands: Commands) {
+    commands.spawn((Text::new("Connecting..."), ConnectionStatusText));
+}
+
+fn spawn_disconnected_status_ui(mut commands: Commands) {
+    commands.spawn((
+        Text::new("Disconnected from server. Reconnecting..."),
+        ConnectionStatusText,
+    ));
+}
+
+fn despawn_connection_status_ui(
+    mut commands: Commands,
+    query: Query<Entity, With<ConnectionStatusText>>,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Clears out remote players left over from the previous connection, so reconnecting
+/// doesn't leave ghosts standing around until the next keyframe happens to evict them.
+fn clear_stale_remote_players(mut commands: Commands, query: Query<Entity, With<Remote>>) {
+    for entity in &query {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// `MainMenu` has no real menu yet, just a prompt; press enter to start connecting.
+///
+/// TODO: actually open the websocket connection here once the client has one.
+fn start_connecting(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        next_state.set(AppState::Connecting);
+    }
+}
+
+/// The handshake is "done" the moment the first `StateSync` frame decodes, since that's
+/// the first time the server has told us about any `GameClient`s (even an empty list).
+fn enter_game_on_first_sync(
+    mut acks: EventReader<ServerStateReceived>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if acks.read().next().is_some() {
+        next_state.set(AppState::InGame);
+    }
+}
+
+fn handle_socket_closed(
+    mut events: EventReader<SocketClosed>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if events.read().next().is_some() {
+        next_state.set(AppState::Disconnected);
+    }
+}
+
+fn reset_reconnect_timer(mut timer: ResMut<ReconnectTimer>) {
+    timer.0.reset();
+}
+
+fn tick_reconnect_timer(
+    time: Res<Time>,
+    mut timer: ResMut<ReconnectTimer>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        next_state.set(AppState::Connecting);
+    }
+}
+
+/// Decodes each `StateSyncReceived` frame and applies it: position updates go
+/// straight into `ClientPositions`' interpolation buffers, while join/leave events
+/// are re-raised as their own `Event`s for `sync_clients_to_players` to act on,
+/// rather than having it re-diff the whole client map every tick.
+fn decode_state_sync(
+    mut raw_frames: EventReader<StateSyncReceived>,
+    mut positions: ResMut<ClientPositions>,
+    mut remote_inputs: ResMut<RemoteInputBuffer>,
+    mut clock: ResMut<ServerClock>,
+    frame: Res<CurrentFrame>,
+    mut acks: EventWriter<ServerStateReceived>,
+    mut joined: EventWriter<ClientJoined>,
+    mut left: EventWriter<ClientLeft>,
+) {
+    for StateSyncReceived(bytes) in raw_frames.read() {
+        let Ok(sync) = bincode::deserialize::<StateSync>(bytes) else {
+            continue;
+        };
+
+        match sync {
+            StateSync::Delta {
+                ack,
+                updates,
+                events,
+                inputs,
+            } => {
+                clock.sync(frame.0, ack.tick);
+                acks.write(ServerStateReceived(ack));
+                for update in updates {
+                    positions.push(update.uuid, update.tick, update.position);
+                }
+                for event in events {
+                    match event {
+                        shared::ClientEvent::Joined(client) => {
+                            positions.push(client.uuid, client.tick, client.position);
+                            joined.write(ClientJoined(client));
+                        }
+                        shared::ClientEvent::Left(uuid) => {
+                            left.write(ClientLeft(uuid));
+                        }
+                    }
+                }
+                for remote in inputs {
+                    remote_inputs.confirm(remote);
+                }
+            }
+            StateSync::Keyframe {
+                ack,
+                clients,
+                inputs,
+            } => {
+                clock.sync(frame.0, ack.tick);
+                acks.write(ServerStateReceived(ack));
+                for client in clients {
+                    positions.push(client.uuid, client.tick, client.position);
+                    joined.write(ClientJoined(client));
+                }
+                for remote in inputs {
+                    remote_inputs.confirm(remote);
+                }
+            }
+        }
+    }
+}
+
+/// Confirmed remote inputs, keyed by the client that sent them. Frames with no entry
+/// yet are predicted by repeating the last confirmed input, per-client.
+///
+/// TODO: populate this from the websocket connection once the client has one; for now
+/// nothing feeds it, so every remote frame predicts from `PlayerInput::default()`.
+#[derive(Resource, Default)]
+struct RemoteInputBuffer {
+    confirmed: HashMap<Uuid, VecDeque<PlayerInput>>,
+    last_confirmed_frame: HashMap<Uuid, u64>,
+}
+
+impl RemoteInputBuffer {
+    /// The input to simulate a remote client with for `frame`: the confirmed input if
+    /// we have it, otherwise the last confirmed input repeated (prediction).
+    fn predict(&self, uuid: Uuid, frame: u64) -> PlayerInput {
+        if let Some(inputs) = self.confirmed.get(&uuid) {
+            if let Some(input) = inputs.iter().find(|input| input.frame == frame) {
+                return *input;
+            }
+            if let Some(last) = inputs.back() {
+                return PlayerInput { frame, ..*last };
+            }
+        }
+        PlayerInput::default()
+    }
+
+    /// Records a confirmed remote input, keeping only the frames `rollback_player`
+    /// could still need to replay.
+    fn confirm(&mut self, remote: shared::RemoteInput) {
+        let inputs = self.confirmed.entry(remote.uuid).or_default();
+        inputs.push_back(remote.input);
+        while inputs.len() > MAX_PREDICTION_FRAMES as usize {
+            inputs.pop_front();
+        }
+        self.last_confirmed_frame
+            .entry(remote.uuid)
+            .and_modify(|frame| *frame = (*frame).max(remote.input.frame))
+            .or_insert(remote.input.frame);
+    }
+}
+
+/// True once the furthest-behind remote peer's last confirmed frame is more than
+/// `MAX_PREDICTION_FRAMES` behind `frame`: the point past which we'd otherwise keep
+/// compounding guesses about peers we haven't heard from in too long. Shared by
+/// `move_player` (stall new simulation) and `rollback_player` (stall replay).
+fn prediction_window_exhausted(frame: u64, remote_inputs: &RemoteInputBuffer) -> bool {
+    let Some(&confirmed_frame) = remote_inputs.last_confirmed_frame.values().min() else {
+        return false;
+    };
+    frame.saturating_sub(confirmed_frame) > MAX_PREDICTION_FRAMES
+}
+
+/// A point-in-time copy of everything rollback needs to restore before resimulating:
+/// the local player's `Transform` and collision state.
+#[derive(Clone, Copy)]
+struct PlayerSnapshot {
+    translation: Vec3,
+    colliding: bool,
+}
+
+/// Per-frame ring buffer of `PlayerSnapshot`s, trimmed to `MAX_PREDICTION_FRAMES` so a
+/// rollback can never reach further back than the window we're willing to predict.
+#[derive(Resource, Default)]
+struct SnapshotBuffer {
+    snapshots: VecDeque<(u64, PlayerSnapshot)>,
+}
+
+impl SnapshotBuffer {
+    fn push(&mut self, frame: u64, snapshot: PlayerSnapshot) {
+        self.snapshots.push_back((frame, snapshot));
+        while self.snapshots.len() > MAX_PREDICTION_FRAMES as usize {
+            self.snapshots.pop_front();
+        }
+    }
+
+    fn get(&self, frame: u64) -> Option<PlayerSnapshot> {
+        self.snapshots
+            .iter()
+            .find(|(f, _)| *f == frame)
+            .map(|(_, snapshot)| *snapshot)
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -184,18 +680,32 @@ fn setup(
 
     // Player
     let player_y = -300.0 + GAP_BETWEEN_PLAYER_AND_FLOOR;
-    commands.spawn((
-        // Transform {
-        //     // Position player forward, in-front of the background
-        //     translation: Vec3::new(0., player_y, 1.),
-        //     scale: PLAYER_SIZE,
-        //     ..default()
-        // },
-        Transform::from_xyz(0.0, 0.5, 0.0),
-        Mesh3d(meshes.add(Sphere::default().mesh())),
-        MeshMaterial3d(debug_material.clone()),
-        Player::default(),
-        Collider,
+    let player = commands
+        .spawn((
+            // Transform {
+            //     // Position player forward, in-front of the background
+            //     translation: Vec3::new(0., player_y, 1.),
+            //     scale: PLAYER_SIZE,
+            //     ..default()
+            // },
+            Transform::from_xyz(0.0, 0.5, 0.0),
+            Mesh3d(meshes.add(Sphere::default().mesh())),
+            MeshMaterial3d(debug_material.clone()),
+            Player::default(),
+            Collider,
+            Local,
+        ))
+        .id();
+
+    // Ball-shaped rigid body so `bevy_rapier3d` can report real contacts against the
+    // ground, walls, and bricks. Movement still goes through `RapierVelocity` rather
+    // than writing `Transform.translation` directly (see `move_player_physics`).
+    #[cfg(feature = "physics")]
+    commands.entity(player).insert((
+        RigidBody::Dynamic,
+        RapierCollider::ball(0.5),
+        RapierVelocity::zero(),
+        ActiveEvents::COLLISION_EVENTS,
     ));
 }
 
@@ -204,10 +714,19 @@ fn setup_map(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    commands.spawn((
-        Mesh3d(meshes.add(Plane3d::default().mesh().size(50.0, 50.0).subdivisions(10))),
-        MeshMaterial3d(materials.add(Color::from(SILVER))),
-    ));
+    let ground = commands
+        .spawn((
+            Mesh3d(meshes.add(Plane3d::default().mesh().size(50.0, 50.0).subdivisions(10))),
+            MeshMaterial3d(materials.add(Color::from(SILVER))),
+        ))
+        .id();
+
+    // Static collider for the ground plane; `setup_map` doesn't spawn walls or bricks
+    // yet, so there's nothing else here to give a `Collider` to.
+    #[cfg(feature = "physics")]
+    commands
+        .entity(ground)
+        .insert((RigidBody::Fixed, RapierCollider::halfspace(Vec3::Y).unwrap()));
     //     commands.spawn((
     //         Mesh3d(meshes.add(Circle::new((MAP_SIZE / 2) as f32))),
     //         MeshMaterial3d(materials.add(Color::WHITE)),
@@ -227,8 +746,8 @@ fn on_resize_system(
 }
 
 fn collide_player(
-    other_players_query: Query<&Transform, (With<Collider>, With<OtherPlayer>, Without<Player>)>,
-    mut player_query: Query<&mut Transform, (With<Collider>, With<Player>, Without<OtherPlayer>)>,
+    other_players_query: Query<&Transform, (With<Collider>, With<Remote>, Without<Local>)>,
+    mut player_query: Query<&mut Transform, (With<Collider>, With<Local>, Without<Remote>)>,
 ) {
     let mut player_transform = player_query.single_mut().unwrap();
     for other_player_transform in other_players_query.iter() {
@@ -251,15 +770,23 @@ fn collide_player(
     }
 }
 
-fn move_player(
+/// Advances the local simulation clock. Runs first in the `FixedUpdate` chain so every
+/// other rollback system agrees on "now".
+fn advance_frame(mut frame: ResMut<CurrentFrame>) {
+    frame.0 += 1;
+}
+
+/// Reads this tick's raw input and buffers it under the current frame, the same shape
+/// `PlayerInput` the server and remote peers will eventually see.
+///
+/// TODO: hand `input` off to the websocket connection once the client has one; today
+/// it's only consumed locally by `move_player` and kept in `LocalInputBuffer` for
+/// rollback resimulation.
+fn buffer_local_input(
     gamepads: Query<&Gamepad>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query_set: ParamSet<(
-        Query<&mut Transform, (With<Player>, Without<OtherPlayer>)>,
-        Query<&Transform, (With<OtherPlayer>, Without<Player>)>,
-        Query<&mut Transform, (With<Camera>, Without<Player>)>,
-    )>,
-    time: Res<Time>,
+    frame: Res<CurrentFrame>,
+    mut local_inputs: ResMut<LocalInputBuffer>,
 ) {
     let mut x = 0.0;
     let mut z = 0.0;
@@ -295,34 +822,178 @@ fn move_player(
         }
     }
 
-    let new_translation = {
-        query_set.p0().single().unwrap().translation.add(
-            Vec3::new(1.0, 0.0, 1.0)
-                .mul(Vec3::new(x, 0.0, z))
-                .mul(PLAYER_SPEED * time.delta_secs()),
-        )
+    let input = PlayerInput {
+        x: x.signum() as i8,
+        z: z.signum() as i8,
+        // Stamped `INPUT_DELAY_FRAMES` ahead of "now", not with `frame.0` itself: this
+        // is the input `move_player` will apply once the local sim actually reaches
+        // that frame, giving the network (and remote peers predicting us) that many
+        // frames' head start before it's due. `move_player` looks it up with
+        // `local_inputs.get(frame.0)`, so the input captured this frame won't be
+        // applied until `INPUT_DELAY_FRAMES` frames from now.
+        frame: frame.0 + INPUT_DELAY_FRAMES,
+    };
+    local_inputs.push(input);
+}
+
+/// Applies a single frame of movement deterministically: `PLAYER_SPEED * FIXED_DT`,
+/// never `time.delta_secs()`, so the same input always produces the same delta on
+/// client, server, and during rollback resimulation alike.
+fn simulate_frame(input: PlayerInput, transform: &mut Transform) {
+    let [x, z] = apply_input([transform.translation.x, transform.translation.z], input);
+    transform.translation.x = x;
+    transform.translation.z = z;
+}
+
+fn move_player(
+    frame: Res<CurrentFrame>,
+    local_inputs: Res<LocalInputBuffer>,
+    remote_inputs: Res<RemoteInputBuffer>,
+    mut snapshots: ResMut<SnapshotBuffer>,
+    mut player_query: Query<(&mut Transform, &Player), (With<Player>, Without<OtherPlayer>)>,
+) {
+    // Stall rather than keep predicting new frames once we're more than
+    // `MAX_PREDICTION_FRAMES` ahead of the slowest-confirming remote peer; otherwise
+    // `rollback_player` would just have to unwind an ever-growing pile of guesses.
+    if prediction_window_exhausted(frame.0, &remote_inputs) {
+        return;
+    }
+
+    let Some(input) = local_inputs.get(frame.0) else {
+        return;
+    };
+
+    let (mut player_transform, player) = player_query.single_mut().unwrap();
+    simulate_frame(input, &mut player_transform);
+
+    snapshots.push(
+        frame.0,
+        PlayerSnapshot {
+            translation: player_transform.translation,
+            colliding: player.colliding,
+        },
+    );
+}
+
+/// The `physics`-feature equivalent of `move_player`: instead of writing
+/// `Transform.translation` directly, it drives the player's rapier rigid body through
+/// `RapierVelocity`, so rapier's own integrator (run `in_fixed_schedule`) both moves
+/// the player and produces real contacts against the ground/walls/bricks.
+#[cfg(feature = "physics")]
+fn move_player_physics(
+    frame: Res<CurrentFrame>,
+    local_inputs: Res<LocalInputBuffer>,
+    mut player_query: Query<&mut RapierVelocity, (With<Local>, Without<Remote>)>,
+) {
+    let Some(input) = local_inputs.get(frame.0) else {
+        return;
     };
+    let mut velocity = player_query.single_mut().unwrap();
+    velocity.linvel = Vec3::new(input.x as f32, 0.0, input.z as f32) * PLAYER_SPEED;
+}
+
+/// Feeds rapier's collision events into the existing `CollisionEvent`/`Player`
+/// machinery, so `Player::colliding` (and eventually `CollisionSound`) react to real
+/// contacts instead of never firing.
+#[cfg(feature = "physics")]
+fn sync_rapier_collisions(
+    mut rapier_events: EventReader<RapierCollisionEvent>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut player_query: Query<&mut Player, With<Local>>,
+) {
+    for event in rapier_events.read() {
+        let started = matches!(event, RapierCollisionEvent::Started(..));
+        if let Ok(mut player) = player_query.single_mut() {
+            player.colliding = started;
+        }
+        if started {
+            collision_events.write(CollisionEvent);
+        }
+    }
+}
 
-    // Check for collision, assume players are round
-    // for other_player in query_set.p1().iter() {
-    //     if new_translation.distance(other_player.translation) < PLAYER_SIZE.x {
-    //         return;
-    //     }
-    // }
+/// Reconciles the local player against the server's authoritative state: snaps to the
+/// server position, then replays every input the server hadn't processed yet so
+/// prediction stays responsive instead of rubber-banding back each time an ack
+/// arrives.
+///
+/// Non-functional stub: this only ever runs off a `ServerStateReceived` event, and
+/// nothing raises one yet (see the `TODO` on its definition) because the client has
+/// no websocket connection. Safe to leave chained into `FixedUpdate` as a no-op; the
+/// replay logic itself is exercised by `rollback_tests` below.
+fn reconcile_with_server(
+    mut events: EventReader<ServerStateReceived>,
+    mut local_inputs: ResMut<LocalInputBuffer>,
+    mut player_query: Query<&mut Transform, (With<Player>, Without<OtherPlayer>)>,
+) {
+    let Some(ServerStateReceived(state)) = events.read().last() else {
+        return;
+    };
 
-    // Now move player
-    let mut player_query = query_set.p0();
     let mut player_transform = player_query.single_mut().unwrap();
-    player_transform.translation = new_translation;
+    player_transform.translation.x = state.position[0];
+    player_transform.translation.z = state.position[1];
 
-    // Set camera center to match player's
-    // let mut cameras = query_set.p2();
-    // let mut cameras = query_set.p2();
-    // for mut transform in cameras.iter_mut() {
-    // for mut camera_transform in query_set.p2().iter_mut() {
-    //     camera_transform.translation.x = new_translation.x;
-    //     camera_transform.translation.y = new_translation.y;
-    // }
+    local_inputs.prune_up_to(state.last_processed_seq);
+    replay_inputs(&mut player_transform, local_inputs.inputs.iter().copied());
+}
+
+/// Replays `inputs`, in order, forward from `transform`'s current position. The core
+/// of reconciliation (and of `rollback_player`'s resimulation), pulled out so it's
+/// unit-testable without a `World`.
+fn replay_inputs(transform: &mut Transform, inputs: impl IntoIterator<Item = PlayerInput>) {
+    for input in inputs {
+        simulate_frame(input, transform);
+    }
+}
+
+/// Restores the snapshot of the last frame every remote peer has confirmed and
+/// resimulates the local player forward from it, on every tick that such a snapshot
+/// is still buffered. It does not compare confirmed remote input against what was
+/// predicted, so it rolls back whether or not a prediction was wrong.
+/// `RemoteInputBuffer` is only ever populated by `decode_state_sync`,
+/// which (like the rest of the netcode here) is still waiting on a real websocket
+/// connection to feed it — see the `TODO`s on `ServerStateReceived`/
+/// `StateSyncReceived` — so this stays a no-op for now, but the restore-then-replay
+/// machinery is in place for when inputs start arriving.
+fn rollback_player(
+    frame: Res<CurrentFrame>,
+    local_inputs: Res<LocalInputBuffer>,
+    remote_inputs: Res<RemoteInputBuffer>,
+    mut snapshots: ResMut<SnapshotBuffer>,
+    mut player_query: Query<&mut Transform, (With<Player>, Without<OtherPlayer>)>,
+) {
+    let Some(&confirmed_frame) = remote_inputs.last_confirmed_frame.values().min() else {
+        return;
+    };
+    // Same window `move_player` stalls new simulation at; past it there's nothing
+    // useful left to roll back to.
+    if prediction_window_exhausted(frame.0, &remote_inputs) {
+        return;
+    }
+    let Some(snapshot) = snapshots.get(confirmed_frame) else {
+        return;
+    };
+
+    let mut player_transform = player_query.single_mut().unwrap();
+    player_transform.translation = snapshot.translation;
+
+    // Re-simulate forward from the confirmed frame with our own buffered local input,
+    // up to but not including the present frame, which `move_player` simulates next
+    // in the chain.
+    for replay_frame in (confirmed_frame + 1)..frame.0 {
+        let Some(input) = local_inputs.get(replay_frame) else {
+            continue;
+        };
+        simulate_frame(input, &mut player_transform);
+        snapshots.push(
+            replay_frame,
+            PlayerSnapshot {
+                translation: player_transform.translation,
+                colliding: false,
+            },
+        );
+    }
 }
 
 fn handle_zoom(
@@ -372,44 +1043,63 @@ fn handle_zoom(
     }
 }
 
+/// Renders each remote player `RENDER_DELAY_FRAMES` behind the newest snapshot we have
+/// for them, interpolating between the two snapshots bracketing that point in time.
+/// This replaces snapping straight to the latest `ClientPositions` entry, which tore
+/// under the 64 Hz send rate and any jitter in arrival time.
 fn update_existing_player_positions(
-    mut query: Query<(&mut Transform, &OtherPlayer)>,
+    mut query: Query<(&mut Transform, &mut NetPosition, &ClientId), With<Remote>>,
     positions: Res<ClientPositions>,
+    frame: Res<CurrentFrame>,
+    clock: Res<ServerClock>,
 ) {
-    for (mut transform, player) in query.iter_mut() {
-        if let Some(pos) = positions.map.get(&player.client.uuid) {
+    // `ClientPositions` snapshots are stamped with the server's global tick, not our
+    // own `CurrentFrame`, so render a point in *that* timeline -- converted through
+    // `ServerClock` -- rather than comparing the two clocks directly.
+    let Some(server_now) = clock.to_server_tick(frame.0) else {
+        return;
+    };
+    let target_tick = server_now.saturating_sub(shared::RENDER_DELAY_FRAMES);
+    for (mut transform, mut net_position, client_id) in query.iter_mut() {
+        let Some(buffer) = positions.map.get(&client_id.0) else {
+            continue;
+        };
+        if let Some(pos) = sample_position_at(buffer, target_tick) {
             transform.translation.x = pos[0];
-            transform.translation.y = pos[1];
+            transform.translation.z = pos[1];
+            net_position.0 = pos;
         }
     }
 }
 
+/// Spawns/despawns remote player entities, driven by `ClientJoined`/`ClientLeft`
+/// events rather than diffing the whole client map every tick: deltas only ever tell
+/// us what changed, so that's the only work this needs to do.
 fn sync_clients_to_players(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
-    query: Query<(Entity, &OtherPlayer)>,
-    clients_pos: Res<ClientPositions>,
+    mut joined: EventReader<ClientJoined>,
+    mut left: EventReader<ClientLeft>,
+    query: Query<(Entity, &ClientId), With<Remote>>,
 ) {
-    // Get existing set of active players
-    // Remove any players that aren't in the active client list
-    let mut player_set = HashSet::<Uuid>::new();
-    for (entity, player) in query.iter() {
-        if clients_pos.map.contains_key(&player.client.uuid) {
-            player_set.insert(player.client.uuid);
-        } else {
+    for ClientLeft(uuid) in left.read() {
+        if let Some((entity, _)) = query.iter().find(|(_, id)| id.0 == *uuid) {
             commands.entity(entity).despawn();
         }
     }
 
-    // Determine new clients by checking against keys in ClientPositions
-    let new_clients = clients_pos
-        .map
-        .iter()
-        .filter(|(&uuid, _)| !player_set.contains(&uuid));
+    for ClientJoined(client) in joined.read() {
+        // A keyframe re-announces every client it knows about, including ones we
+        // already spawned from an earlier delta; don't double-spawn them.
+        if query.iter().any(|(_, id)| id.0 == client.uuid) {
+            continue;
+        }
 
-    // Add new clients
-    for (uuid, position) in new_clients {
+        let position = client.position;
+        // `GameClient` is only ever a wire DTO: split it straight into the
+        // components the entity actually carries.
+        let (client_id, net_position) = client.clone().into_components();
         commands
             .spawn((
                 Transform {
@@ -420,12 +1110,10 @@ fn sync_clients_to_players(
                 Mesh2d(meshes.add(Circle::default())),
                 MeshMaterial2d(materials.add(ColorMaterial::from(PLAYER_COLOR))),
                 Collider,
-                OtherPlayer {
-                    client: GameClient {
-                        uuid: uuid.clone(),
-                        position: position.clone(),
-                    },
-                },
+                OtherPlayer,
+                Remote,
+                client_id,
+                net_position,
             ))
             // Add text to display other player name/id
             .with_children(|parent| {
@@ -464,3 +1152,139 @@ fn apply_velocity(mut query: Query<(&mut Transform, &Velocity)>, time: Res<Time>
         transform.translation.y += velocity.y * time.delta_secs();
     }
 }
+
+#[cfg(test)]
+mod rollback_tests {
+    use super::*;
+
+    fn input(frame: u64, x: i8) -> PlayerInput {
+        PlayerInput { x, z: 0, frame }
+    }
+
+    #[test]
+    fn local_input_buffer_trims_to_the_delay_plus_prediction_window() {
+        let mut buffer = LocalInputBuffer::default();
+        let cap = (INPUT_DELAY_FRAMES + MAX_PREDICTION_FRAMES) as u64;
+        for frame in 0..(cap + 5) {
+            buffer.push(input(frame, 1));
+        }
+        assert_eq!(buffer.inputs.len() as u64, cap);
+        // The oldest entries should have been dropped, not the newest.
+        assert!(buffer.get(0).is_none());
+        assert!(buffer.get(cap + 4).is_some());
+    }
+
+    #[test]
+    fn local_input_buffer_prune_up_to_drops_confirmed_frames_only() {
+        let mut buffer = LocalInputBuffer::default();
+        for frame in 0..5 {
+            buffer.push(input(frame, 1));
+        }
+        buffer.prune_up_to(2);
+        assert!(buffer.get(0).is_none());
+        assert!(buffer.get(2).is_none());
+        assert!(buffer.get(3).is_some());
+        assert!(buffer.get(4).is_some());
+    }
+
+    #[test]
+    fn snapshot_buffer_trims_to_max_prediction_frames() {
+        let mut buffer = SnapshotBuffer::default();
+        let snapshot = PlayerSnapshot {
+            translation: Vec3::ZERO,
+            colliding: false,
+        };
+        for frame in 0..(MAX_PREDICTION_FRAMES + 5) {
+            buffer.push(frame, snapshot);
+        }
+        assert_eq!(buffer.snapshots.len() as u64, MAX_PREDICTION_FRAMES);
+        assert!(buffer.get(0).is_none());
+        assert!(buffer.get(MAX_PREDICTION_FRAMES + 4).is_some());
+    }
+
+    #[test]
+    fn remote_input_buffer_predicts_last_confirmed_input_when_frame_unseen() {
+        let mut buffer = RemoteInputBuffer::default();
+        let uuid = Uuid::new_v4();
+        buffer.confirm(shared::RemoteInput {
+            uuid,
+            input: input(3, 1),
+        });
+
+        // Exact frame: returns what was confirmed for it.
+        assert_eq!(buffer.predict(uuid, 3), input(3, 1));
+        // Later, unconfirmed frame: repeats the last confirmed input.
+        assert_eq!(buffer.predict(uuid, 10), input(10, 1));
+        // No input confirmed yet for this uuid at all: predicts a neutral default.
+        assert_eq!(buffer.predict(Uuid::new_v4(), 10), PlayerInput::default());
+    }
+
+    #[test]
+    fn prediction_window_exhausted_stalls_past_max_prediction_frames() {
+        let mut buffer = RemoteInputBuffer::default();
+        let uuid = Uuid::new_v4();
+        buffer.confirm(shared::RemoteInput {
+            uuid,
+            input: input(0, 0),
+        });
+
+        assert!(!prediction_window_exhausted(MAX_PREDICTION_FRAMES, &buffer));
+        assert!(prediction_window_exhausted(
+            MAX_PREDICTION_FRAMES + 1,
+            &buffer
+        ));
+    }
+
+    #[test]
+    fn prediction_window_never_exhausted_with_no_confirmed_peers() {
+        let buffer = RemoteInputBuffer::default();
+        assert!(!prediction_window_exhausted(u64::MAX, &buffer));
+    }
+
+    #[test]
+    fn server_clock_converts_local_frames_into_the_server_tick_domain() {
+        let mut clock = ServerClock::default();
+        assert_eq!(clock.to_server_tick(100), None);
+
+        // Server was already at tick 500 when we were at local frame 10.
+        clock.sync(10, 500);
+        assert_eq!(clock.to_server_tick(10), Some(500));
+        assert_eq!(clock.to_server_tick(15), Some(505));
+    }
+
+    fn snapshots(entries: &[(u64, [f32; 2])]) -> VecDeque<(u64, [f32; 2])> {
+        entries.iter().copied().collect()
+    }
+
+    #[test]
+    fn sample_position_interpolates_between_bracketing_snapshots() {
+        let buffer = snapshots(&[(0, [0.0, 0.0]), (10, [10.0, 0.0])]);
+        assert_eq!(sample_position_at(&buffer, 5), Some([5.0, 0.0]));
+    }
+
+    #[test]
+    fn sample_position_extrapolates_a_bounded_distance_past_the_newest_snapshot() {
+        let buffer = snapshots(&[(0, [0.0, 0.0]), (10, [10.0, 0.0])]);
+        let gap = shared::MAX_EXTRAPOLATION_FRAMES;
+        // Within the extrapolation budget: keeps projecting the same velocity.
+        let within = sample_position_at(&buffer, 10 + gap).unwrap();
+        assert_eq!(within, [10.0 + gap as f32, 0.0]);
+        // Past the budget: holds the newest known position instead of compounding.
+        let past = sample_position_at(&buffer, 10 + gap + 1).unwrap();
+        assert_eq!(past, [10.0, 0.0]);
+    }
+
+    #[test]
+    fn sample_position_holds_oldest_snapshot_when_target_predates_the_buffer() {
+        let buffer = snapshots(&[(10, [1.0, 1.0]), (20, [2.0, 2.0])]);
+        assert_eq!(sample_position_at(&buffer, 0), Some([1.0, 1.0]));
+    }
+
+    #[test]
+    fn replay_inputs_applies_each_buffered_input_in_order() {
+        let mut transform = Transform::from_xyz(0.0, 0.0, 0.0);
+        replay_inputs(&mut transform, [input(1, 1), input(2, 1)]);
+        assert_eq!(transform.translation.x, 2.0 * PLAYER_SPEED * FIXED_DT);
+        assert_eq!(transform.translation.z, 0.0);
+    }
+}
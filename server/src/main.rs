@@ -11,27 +11,181 @@ use rand::distributions::{Alphanumeric, DistString};
 use serde::{Deserialize, Serialize};
 use serde_json::Result as SerdeResult;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 
 // The shared library between server and client
-use shared::{GameClient, PositionEvent};
+use shared::{
+    apply_input, AuthoritativeState, ClientEvent, GameClient, PlayerInput, PositionUpdate,
+    RemoteInput, StateSync, KEYFRAME_INTERVAL,
+};
 
 #[derive(Clone, Debug)]
 struct GameState {
     clients: HashMap<Uuid, GameClient>,
+    // Advanced at `shared::FIXED_HZ` by the task spawned in `main`, and stamped onto
+    // the `GameClient` that moved so clients can interpolate remote players against a
+    // shared timeline.
+    tick: u64,
+    // Highest `PlayerInput::frame` the server has applied for each client, echoed
+    // back as `AuthoritativeState::last_processed_seq` so that client can discard
+    // everything up to and including it from its unacknowledged input queue.
+    last_processed_seq: HashMap<Uuid, u64>,
+    // Inputs relayed to each client but not yet drained into a `StateSync` for them,
+    // keyed by the *receiving* client. Every other client's input lands in here the
+    // moment it's applied, so remote-prediction on the receiving end has something to
+    // predict with instead of assuming peers are holding still.
+    pending_relay: HashMap<Uuid, Vec<RemoteInput>>,
 }
 impl GameState {
     fn new() -> GameState {
         return GameState {
             clients: HashMap::new(),
+            tick: 0,
+            last_processed_seq: HashMap::new(),
+            pending_relay: HashMap::new(),
         };
     }
 }
 
+/// What the server sends back after applying a client's input: the authoritative ack
+/// for that client's own prediction, plus the other clients' positions to render.
+#[derive(Serialize)]
+struct ServerMessage<'a> {
+    ack: AuthoritativeState,
+    clients: Vec<&'a GameClient>,
+}
+
 type SharedGameState = Arc<Mutex<GameState>>;
 
+/// Applies one client's input authoritatively and records it as the highest input
+/// seen for that client so far. Shared by both the legacy JSON path and the binary
+/// path below, which only differ in how they encode the reply.
+fn apply_client_input(state: &SharedGameState, client_id: Uuid, input: PlayerInput) {
+    let mut state = state.lock().expect("Couldn't acquire state lock!");
+    // `state.tick` itself is advanced by the fixed-cadence task spawned in `main`, not
+    // here: ticking it once per processed input made the clock run at however fast
+    // inputs happened to arrive, which isn't comparable to a client's evenly-spaced
+    // `CurrentFrame`.
+    let tick = state.tick;
+    let client = state
+        .clients
+        .get_mut(&client_id)
+        .expect("Couldn't find previously created client");
+
+    // Same movement math the client predicts with, so reconciliation converges
+    // instead of fighting the client every tick.
+    client.position = apply_input(client.position, input);
+    client.tick = tick;
+
+    state
+        .last_processed_seq
+        .entry(client_id)
+        .and_modify(|seq| *seq = (*seq).max(input.frame))
+        .or_insert(input.frame);
+
+    // Relay this input to every other connected client, so their remote-prediction
+    // has the real thing to replay instead of repeating `client_id`'s last known input
+    // forever. Queued per-receiver and drained the next time that receiver's
+    // connection builds a `StateSync`.
+    let relayed = RemoteInput {
+        uuid: client_id,
+        input,
+    };
+    let other_ids: Vec<Uuid> = state
+        .clients
+        .keys()
+        .filter(|&&id| id != client_id)
+        .copied()
+        .collect();
+    for id in other_ids {
+        state.pending_relay.entry(id).or_default().push(relayed);
+    }
+}
+
+/// Drains and returns the inputs relayed to `client_id` since the last time this was
+/// called for it.
+fn drain_relayed_inputs(state: &SharedGameState, client_id: Uuid) -> Vec<RemoteInput> {
+    let mut state = state.lock().expect("Couldn't acquire state lock!");
+    state.pending_relay.remove(&client_id).unwrap_or_default()
+}
+
+fn build_ack(state: &SharedGameState, client_id: Uuid) -> AuthoritativeState {
+    let state = state.lock().expect("Couldn't acquire state lock!");
+    AuthoritativeState {
+        uuid: client_id,
+        position: state
+            .clients
+            .get(&client_id)
+            .map(|c| c.position)
+            .unwrap_or_default(),
+        last_processed_seq: *state.last_processed_seq.get(&client_id).unwrap_or(&0),
+        tick: state.tick,
+    }
+}
+
+/// Builds the `StateSync` frame for one receiver: a full `Keyframe` when
+/// `force_keyframe` is set (which also resets this receiver's delta-sync
+/// bookkeeping), or a `Delta` against what it's already been told otherwise. Kept
+/// free of the socket/mutex plumbing in `handle_connection` so the diffing logic can
+/// be unit-tested on its own.
+fn build_state_sync(
+    ack: AuthoritativeState,
+    others: &[GameClient],
+    known_clients: &mut HashSet<Uuid>,
+    last_sent_positions: &mut HashMap<Uuid, [f32; 2]>,
+    force_keyframe: bool,
+    inputs: Vec<RemoteInput>,
+) -> StateSync {
+    if force_keyframe {
+        *known_clients = others.iter().map(|c| c.uuid).collect();
+        *last_sent_positions = others.iter().map(|c| (c.uuid, c.position)).collect();
+        return StateSync::Keyframe {
+            ack,
+            clients: others.to_vec(),
+            inputs,
+        };
+    }
+
+    let mut updates = Vec::new();
+    let mut events = Vec::new();
+    let mut seen = HashSet::new();
+
+    for client in others {
+        seen.insert(client.uuid);
+        if known_clients.insert(client.uuid) {
+            events.push(ClientEvent::Joined(client.clone()));
+        }
+        if last_sent_positions.get(&client.uuid) != Some(&client.position) {
+            last_sent_positions.insert(client.uuid, client.position);
+            updates.push(PositionUpdate {
+                uuid: client.uuid,
+                position: client.position,
+                tick: client.tick,
+            });
+        }
+    }
+
+    // Clients we'd told this receiver about that are no longer here.
+    known_clients.retain(|uuid| {
+        if seen.contains(uuid) {
+            true
+        } else {
+            events.push(ClientEvent::Left(*uuid));
+            last_sent_positions.remove(uuid);
+            false
+        }
+    });
+
+    StateSync::Delta {
+        ack,
+        updates,
+        events,
+        inputs,
+    }
+}
+
 async fn handle_connection(stream: TcpStream, state: SharedGameState) -> TungResult<()> {
     let addr = stream
         .peer_addr()
@@ -51,6 +205,7 @@ async fn handle_connection(stream: TcpStream, state: SharedGameState) -> TungRes
             GameClient {
                 uuid: client_id.clone(),
                 position: [0.0, 0.0],
+                tick: 0,
             },
         );
     }
@@ -58,40 +213,77 @@ async fn handle_connection(stream: TcpStream, state: SharedGameState) -> TungRes
     // Closure that removes the client
     let close_client = || -> Result<GameClient, &str> {
         let mut state = state.lock().expect("Couldn't acquire state lock!");
+        state.pending_relay.remove(&client_id);
         return state.clients.remove(&client_id).ok_or("Client not found");
     };
 
+    // Per-connection delta-sync bookkeeping for the binary protocol below: what this
+    // receiver has already been sent, so we only need to send what changed.
+    let mut last_sent_positions: HashMap<Uuid, [f32; 2]> = HashMap::new();
+    let mut known_clients: HashSet<Uuid> = HashSet::new();
+    let mut messages_since_keyframe: u32 = 0;
+
     // Parse received message
     let (mut write, mut read) = ws_stream.split();
     while let Some(msg) = read.next().await {
         match msg? {
             Message::Text(msg) => {
-                if let Ok(pos) = serde_json::from_str::<PositionEvent>(&msg) {
-                    let mut state = state.lock().expect("Couldn't acquire state lock!");
-                    let client = state
-                        .clients
-                        .get_mut(&client_id)
-                        .expect("Couldn't find previously created client");
-
-                    // Set position
-                    client.position = [pos.x, pos.y];
+                // Legacy JSON path, kept around as a debugging fallback: full state,
+                // no delta-compression or keyframing.
+                if let Ok(input) = serde_json::from_str::<PlayerInput>(&msg) {
+                    apply_client_input(&state, client_id, input);
                 }
 
-                // TODO: Move this someplace else
-                // Now we're going to respond with serialized game state
                 let msg = {
+                    let ack = build_ack(&state, client_id);
                     let state = state.lock().expect("Couldn't acquire state lock!");
-
-                    // Only send other clients
                     let clients =
                         Vec::from_iter(state.clients.values().filter(|x| x.uuid != client_id));
 
                     // Return serialized string
-                    serde_json::to_string(&clients).expect("asd")
+                    serde_json::to_string(&ServerMessage { ack, clients }).expect("asd")
                 };
 
                 write.send(Message::text(msg)).await?;
             }
+            Message::Binary(bytes) => {
+                // The server is authoritative over movement: clients send the input
+                // they pressed, never a position, so there's nothing for a client to
+                // lie about.
+                if let Ok(input) = bincode::deserialize::<PlayerInput>(&bytes) {
+                    apply_client_input(&state, client_id, input);
+                }
+
+                let ack = build_ack(&state, client_id);
+                messages_since_keyframe += 1;
+                let force_keyframe = messages_since_keyframe >= KEYFRAME_INTERVAL;
+                if force_keyframe {
+                    messages_since_keyframe = 0;
+                }
+
+                let relayed_inputs = drain_relayed_inputs(&state, client_id);
+                let others: Vec<GameClient> = {
+                    let state = state.lock().expect("Couldn't acquire state lock!");
+                    state
+                        .clients
+                        .values()
+                        .filter(|c| c.uuid != client_id)
+                        .cloned()
+                        .collect()
+                };
+
+                let sync = build_state_sync(
+                    ack,
+                    &others,
+                    &mut known_clients,
+                    &mut last_sent_positions,
+                    force_keyframe,
+                    relayed_inputs,
+                );
+
+                let msg = bincode::serialize(&sync).expect("bincode encode");
+                write.send(Message::binary(msg)).await?;
+            }
             Message::Close(_) => {
                 if close_client().is_err() {
                     panic!("Couldn't remove client!")
@@ -125,6 +317,20 @@ async fn main() -> Result<(), SendSyncError> {
         }
     });
 
+    // Advances `GameState::tick` at `shared::FIXED_HZ`, independent of how often
+    // clients actually send input, so it's an evenly-spaced clock clients can line up
+    // their own `FixedUpdate`-driven frame counter against (see `AuthoritativeState::
+    // tick`). Ticking it inline in `apply_client_input` instead would make the clock
+    // run at whatever rate messages happened to arrive.
+    let tick_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(time::Duration::from_secs_f32(shared::FIXED_DT));
+        loop {
+            interval.tick().await;
+            tick_state.lock().expect("Couldn't acquire state lock!").tick += 1;
+        }
+    });
+
     // Accept connections
     loop {
         let (stream, _) = listener.accept().await?;
@@ -134,3 +340,207 @@ async fn main() -> Result<(), SendSyncError> {
         tokio::spawn(handle_connection(stream, state));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(uuid: Uuid, position: [f32; 2], tick: u64) -> GameClient {
+        GameClient {
+            uuid,
+            position,
+            tick,
+        }
+    }
+
+    fn ack(uuid: Uuid) -> AuthoritativeState {
+        AuthoritativeState {
+            uuid,
+            position: [0.0, 0.0],
+            last_processed_seq: 0,
+            tick: 0,
+        }
+    }
+
+    #[test]
+    fn keyframe_reports_every_other_client_and_resets_bookkeeping() {
+        let other = Uuid::new_v4();
+        let others = vec![client(other, [1.0, 2.0], 5)];
+        let mut known_clients = HashSet::new();
+        let mut last_sent_positions = HashMap::new();
+
+        let sync = build_state_sync(
+            ack(other),
+            &others,
+            &mut known_clients,
+            &mut last_sent_positions,
+            true,
+            Vec::new(),
+        );
+
+        match sync {
+            StateSync::Keyframe { clients, .. } => assert_eq!(
+                clients
+                    .iter()
+                    .map(|c| (c.uuid, c.position, c.tick))
+                    .collect::<Vec<_>>(),
+                others
+                    .iter()
+                    .map(|c| (c.uuid, c.position, c.tick))
+                    .collect::<Vec<_>>()
+            ),
+            StateSync::Delta { .. } => panic!("expected a keyframe"),
+        }
+        assert!(known_clients.contains(&other));
+        assert_eq!(last_sent_positions.get(&other), Some(&[1.0, 2.0]));
+    }
+
+    #[test]
+    fn delta_only_reports_changed_positions() {
+        let other = Uuid::new_v4();
+        let mut known_clients = HashSet::from([other]);
+        let mut last_sent_positions = HashMap::from([(other, [0.0, 0.0])]);
+
+        // Unchanged position: no update reported.
+        let unchanged = vec![client(other, [0.0, 0.0], 1)];
+        let sync = build_state_sync(
+            ack(other),
+            &unchanged,
+            &mut known_clients,
+            &mut last_sent_positions,
+            false,
+            Vec::new(),
+        );
+        match sync {
+            StateSync::Delta { updates, events, .. } => {
+                assert!(updates.is_empty());
+                assert!(events.is_empty());
+            }
+            StateSync::Keyframe { .. } => panic!("expected a delta"),
+        }
+
+        // Moved position: reported exactly once, and bookkeeping tracks the new value.
+        let moved = vec![client(other, [3.0, 4.0], 2)];
+        let sync = build_state_sync(
+            ack(other),
+            &moved,
+            &mut known_clients,
+            &mut last_sent_positions,
+            false,
+            Vec::new(),
+        );
+        match sync {
+            StateSync::Delta { updates, .. } => {
+                assert_eq!(updates.len(), 1);
+                assert_eq!(updates[0].position, [3.0, 4.0]);
+            }
+            StateSync::Keyframe { .. } => panic!("expected a delta"),
+        }
+        assert_eq!(last_sent_positions.get(&other), Some(&[3.0, 4.0]));
+    }
+
+    #[test]
+    fn delta_reports_joins_and_leaves() {
+        let joining = Uuid::new_v4();
+        let leaving = Uuid::new_v4();
+        let mut known_clients = HashSet::from([leaving]);
+        let mut last_sent_positions = HashMap::from([(leaving, [0.0, 0.0])]);
+
+        // `leaving` is gone, `joining` just appeared.
+        let others = vec![client(joining, [1.0, 1.0], 1)];
+        let sync = build_state_sync(
+            ack(joining),
+            &others,
+            &mut known_clients,
+            &mut last_sent_positions,
+            false,
+            Vec::new(),
+        );
+
+        let StateSync::Delta { events, .. } = sync else {
+            panic!("expected a delta");
+        };
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, ClientEvent::Joined(c) if c.uuid == joining)));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, ClientEvent::Left(uuid) if *uuid == leaving)));
+        assert!(!known_clients.contains(&leaving));
+        assert!(known_clients.contains(&joining));
+    }
+
+    #[test]
+    fn relayed_inputs_pass_through_to_delta_and_keyframe() {
+        let other = Uuid::new_v4();
+        let sender = Uuid::new_v4();
+        let relayed = vec![RemoteInput {
+            uuid: sender,
+            input: PlayerInput {
+                x: 1,
+                z: 0,
+                frame: 7,
+            },
+        }];
+
+        let mut known_clients = HashSet::new();
+        let mut last_sent_positions = HashMap::new();
+        let sync = build_state_sync(
+            ack(other),
+            &[],
+            &mut known_clients,
+            &mut last_sent_positions,
+            false,
+            relayed.clone(),
+        );
+        let StateSync::Delta { inputs, .. } = sync else {
+            panic!("expected a delta");
+        };
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].uuid, sender);
+
+        let sync = build_state_sync(
+            ack(other),
+            &[],
+            &mut known_clients,
+            &mut last_sent_positions,
+            true,
+            relayed,
+        );
+        let StateSync::Keyframe { inputs, .. } = sync else {
+            panic!("expected a keyframe");
+        };
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(inputs[0].uuid, sender);
+    }
+
+    #[test]
+    fn apply_client_input_queues_relay_for_every_other_client() {
+        let state: SharedGameState = Arc::new(Mutex::new(GameState::new()));
+        let sender = Uuid::new_v4();
+        let bystander = Uuid::new_v4();
+        {
+            let mut state = state.lock().unwrap();
+            state.clients.insert(sender, client(sender, [0.0, 0.0], 0));
+            state
+                .clients
+                .insert(bystander, client(bystander, [0.0, 0.0], 0));
+        }
+
+        apply_client_input(
+            &state,
+            sender,
+            PlayerInput {
+                x: 1,
+                z: 0,
+                frame: 0,
+            },
+        );
+
+        let relayed = drain_relayed_inputs(&state, bystander);
+        assert_eq!(relayed.len(), 1);
+        assert_eq!(relayed[0].uuid, sender);
+        // The sender itself never gets its own input relayed back to it.
+        assert!(drain_relayed_inputs(&state, sender).is_empty());
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "ecs")]
+use bevy_ecs::prelude::Component;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -11,4 +13,167 @@ pub struct PositionEvent {
 pub struct GameClient {
     pub uuid: Uuid,
     pub position: [f32; 2],
+    /// The server's tick when `position` was recorded, so clients can place remote
+    /// players in time (and interpolate between snapshots) instead of snapping to
+    /// whatever update arrived most recently.
+    pub tick: u64,
 }
+
+impl GameClient {
+    /// Splits the wire DTO into the ECS components it's stored as once ingested, so
+    /// systems that only need one piece (say, position for collision) don't have to
+    /// query the whole blob.
+    pub fn into_components(self) -> (ClientId, NetPosition) {
+        (ClientId(self.uuid), NetPosition(self.position))
+    }
+}
+
+/// A client's stable identity, carried on its ECS entity separately from its
+/// position so identity-only queries don't pull position along for the ride.
+///
+/// Only the client ever stores these as actual ECS components (hence `Component`
+/// being behind the `ecs` feature, which the server doesn't enable): the server uses
+/// `ClientId`/`NetPosition`/`Local`/`Remote` as plain data, if at all, and shouldn't
+/// have to compile `bevy_ecs` just because the client's entities carry them.
+#[cfg_attr(feature = "ecs", derive(Component))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClientId(pub Uuid);
+
+/// A client's last known network position, decoupled from `ClientId` so position-only
+/// queries (collision, rendering) don't have to touch identity.
+#[cfg_attr(feature = "ecs", derive(Component))]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NetPosition(pub [f32; 2]);
+
+/// Marks the entity driven by this machine's own input.
+#[cfg_attr(feature = "ecs", derive(Component))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Local;
+
+/// Marks an entity representing another client's player.
+#[cfg_attr(feature = "ecs", derive(Component))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Remote;
+
+// Rollback netcode: the `FixedUpdate` schedule runs at this rate on both client and
+// server, so frame numbers on one side always mean the same amount of simulated
+// time on the other.
+pub const FIXED_HZ: f32 = 64.0;
+pub const FIXED_DT: f32 = 1.0 / FIXED_HZ;
+
+/// How many frames ahead of "now" a client's own input is stamped for, giving the server
+/// (and remote peers predicting this client) a chance to receive it before it's due.
+pub const INPUT_DELAY_FRAMES: u64 = 2;
+
+/// How far a client will predict ahead of the last confirmed frame before it stalls
+/// the simulation rather than keep compounding mispredictions.
+pub const MAX_PREDICTION_FRAMES: u64 = 12;
+
+/// A single frame of movement input. `x`/`z` are axis bits in `[-1, 1]`, matching the
+/// `KeyCode::Key{A,D,S,W}` / left-stick reads `buffer_local_input` does, just
+/// snapshotted per frame instead of read fresh off `Res<ButtonInput<KeyCode>>`.
+///
+/// `frame` doubles as the input's sequence number: it's assigned once, monotonically,
+/// by the client that produced it, which is exactly what server reconciliation needs
+/// to know which inputs it has already applied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerInput {
+    pub x: i8,
+    pub z: i8,
+    pub frame: u64,
+}
+
+/// The movement speed both client prediction and server authority simulate with.
+/// Keeping this (and `apply_input`) in `shared` is what makes reconciliation replay
+/// produce bit-for-bit the same position the server would have computed.
+pub const PLAYER_SPEED: f32 = 50.0;
+
+/// Applies one frame of input to a position. The only movement math either side is
+/// allowed to have; the client uses it for prediction and replay, the server for
+/// authoritative simulation.
+pub fn apply_input(position: [f32; 2], input: PlayerInput) -> [f32; 2] {
+    [
+        position[0] + input.x as f32 * PLAYER_SPEED * FIXED_DT,
+        position[1] + input.z as f32 * PLAYER_SPEED * FIXED_DT,
+    ]
+}
+
+/// Sent by the server in reply to a client's input: the authoritative position after
+/// applying it, the highest input sequence number (`PlayerInput::frame`) the server
+/// had processed for that client so far, and the server's current global tick.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct AuthoritativeState {
+    pub uuid: Uuid,
+    pub position: [f32; 2],
+    pub last_processed_seq: u64,
+    /// The server's global tick at the moment this ack was built. `GameClient`/
+    /// `PositionUpdate` snapshots are stamped from the same counter, so a client can
+    /// compare its own local frame against this to place remote snapshots on a shared
+    /// timeline instead of treating the two as the same clock.
+    pub tick: u64,
+}
+
+/// One remote client's position changing, as carried by `StateSync::Delta`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PositionUpdate {
+    pub uuid: Uuid,
+    pub position: [f32; 2],
+    pub tick: u64,
+}
+
+/// A client joining or leaving, as carried by `StateSync::Delta`. Deltas only cover
+/// position *changes*, so joins/leaves need to be called out explicitly rather than
+/// inferred from a client's absence in `updates`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ClientEvent {
+    Joined(GameClient),
+    Left(Uuid),
+}
+
+/// The bincode-encoded, binary equivalent of re-sending the full `Vec<GameClient>` as
+/// JSON on every message. Sent as a `Message::Binary` frame; see `KEYFRAME_INTERVAL`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum StateSync {
+    /// Only the clients whose position changed since the receiver's last acknowledged
+    /// tick, plus any joins/leaves since then.
+    Delta {
+        ack: AuthoritativeState,
+        updates: Vec<PositionUpdate>,
+        events: Vec<ClientEvent>,
+        /// Other clients' inputs relayed since this receiver's last message, so it can
+        /// predict them forward instead of assuming they're holding still.
+        inputs: Vec<RemoteInput>,
+    },
+    /// A full snapshot of every known client, so a receiver that missed a delta (or
+    /// just connected) can resync instead of drifting forever.
+    Keyframe {
+        ack: AuthoritativeState,
+        clients: Vec<GameClient>,
+        /// Same relayed inputs as `Delta::inputs`; a keyframe still needs these so a
+        /// freshly (re)synced receiver doesn't lose whatever arrived since its last ack.
+        inputs: Vec<RemoteInput>,
+    },
+}
+
+/// How many state-sync messages pass between full keyframes; roughly once a second
+/// at `FIXED_HZ`.
+pub const KEYFRAME_INTERVAL: u32 = 64;
+
+/// An input as relayed by the server: whose input it was, in addition to the input
+/// itself.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RemoteInput {
+    pub uuid: Uuid,
+    pub input: PlayerInput,
+}
+
+/// How many snapshots of history clients keep per remote player for interpolation.
+pub const SNAPSHOT_BUFFER_LEN: usize = 12;
+
+/// Remote players are rendered this many ticks behind the newest snapshot (~94 ms at
+/// `FIXED_HZ`), so there's almost always a pair of snapshots to interpolate between.
+pub const RENDER_DELAY_FRAMES: u64 = 6;
+
+/// How many ticks of extrapolation to tolerate past the newest snapshot before
+/// holding the remote player in place instead of compounding a guess.
+pub const MAX_EXTRAPOLATION_FRAMES: u64 = 4;